@@ -0,0 +1,403 @@
+//! Filesystem abstraction used by `Plan`, so planning and execution can be
+//! unit-tested without touching real disk or real symlinks.
+//!
+//! `RealFs` forwards every call to `std::fs` (and `std::os::unix::fs` for
+//! symlinks) and is what `main` wires up. `FakeFs` is an in-memory fake
+//! backed by a `BTreeMap<PathBuf, Node>`, used in tests to build a directory
+//! tree and exercise `Plan::new`/`Plan::execute` deterministically.
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{fs, os::unix};
+
+#[cfg(test)]
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+
+/// A directory entry as seen by an `Fs` implementation. Mirrors the slice of
+/// `std::fs::DirEntry` that `variant::resolve` and `Plan::new` need, so both
+/// can work the same way against `RealFs` and `FakeFs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DirEntry {
+    path: PathBuf,
+}
+
+impl DirEntry {
+    /// The entry's full path.
+    pub(crate) fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// The entry's bare file name, stripped of its parent directory.
+    pub(crate) fn file_name(&self) -> OsString {
+        self.path.file_name().expect("entry to have a file name").to_os_string()
+    }
+}
+
+/// Filesystem operations used by `Plan`, abstracted behind a trait so tests
+/// can swap in `FakeFs` instead of `RealFs`.
+pub(crate) trait Fs {
+    /// Whether something exists at `path`, following symlinks.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Whether `path` exists and is a directory, following symlinks.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// List the immediate children of the directory at `path`.
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>>;
+
+    /// Read the target of the symlink at `path`, if it is one.
+    fn read_link(&self, path: &Path) -> Option<PathBuf>;
+
+    /// Resolve `path` to an absolute, symlink-free path.
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf>;
+
+    /// Create a symlink at `link` pointing to `original`.
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()>;
+
+    /// Rename (move) `from` to `to`.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Remove the directory at `path` and everything inside it.
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+
+    /// Remove the non-directory entry (file or symlink) at `path`.
+    fn remove_file(&self, path: &Path) -> Result<()>;
+
+    /// Create a directory at `path` (its parent is assumed to already exist).
+    fn create_dir(&self, path: &Path) -> Result<()>;
+
+    /// Copy the contents of the file at `from` to `to`.
+    fn copy_file(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Read the permission mode bits of the file at `path`.
+    fn mode(&self, path: &Path) -> Result<u32>;
+
+    /// Set the permission mode bits of the file at `path`.
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()>;
+
+    /// Read the file at `path` into a `String`.
+    fn read_to_string(&self, path: &Path) -> Result<String>;
+}
+
+/// `Fs` implementation backed by the real filesystem.
+pub(crate) struct RealFs;
+
+impl Fs for RealFs {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>> {
+        fs::read_dir(path)
+            .context(format!("Could not read {:?}", path))?
+            .map(|entry| Ok(DirEntry { path: entry?.path() }))
+            .collect()
+    }
+
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        fs::read_link(path).ok()
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        fs::canonicalize(path).context(format!("failed to canonicalize {path:?}"))
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        // NOTE: This makes the binary unix-only ¯\_(ツ)_/¯.
+        unix::fs::symlink(original, link)?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(from, to)?;
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(path)?;
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        fs::create_dir(path)?;
+        Ok(())
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::copy(from, to).context(format!("failed to copy {from:?} to {to:?}"))?;
+        Ok(())
+    }
+
+    fn mode(&self, path: &Path) -> Result<u32> {
+        use std::os::unix::fs::PermissionsExt;
+
+        Ok(fs::metadata(path).context(format!("failed to read metadata of {path:?}"))?.permissions().mode())
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        Ok(())
+    }
+
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        fs::read_to_string(path).context(format!("failed to read {path:?}"))
+    }
+}
+
+/// A node in `FakeFs`'s in-memory tree.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node {
+    /// A plain file with the given permission mode and text contents.
+    File { mode: u32, contents: String },
+    Dir,
+    /// A symlink pointing at `target`, which may be relative to its parent.
+    Symlink(PathBuf),
+}
+
+/// Default mode given to files registered via `FakeFs::with_file`, matching
+/// the common non-executable file mode.
+#[cfg(test)]
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// In-memory `Fs` fake for tests, backed by a `BTreeMap<PathBuf, Node>`.
+/// Build one with `FakeFs::new` and the `with_*` builder methods, then pass
+/// `&fake as &dyn Fs` wherever `Plan` wants a filesystem.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct FakeFs {
+    nodes: RefCell<BTreeMap<PathBuf, Node>>,
+}
+
+#[cfg(test)]
+impl FakeFs {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a plain file at `path` with the default mode and empty contents.
+    pub(crate) fn with_file(self, path: impl Into<PathBuf>) -> Self {
+        self.with_file_mode(path, DEFAULT_FILE_MODE)
+    }
+
+    /// Register a plain file at `path` with an explicit permission `mode` and
+    /// empty contents.
+    pub(crate) fn with_file_mode(self, path: impl Into<PathBuf>, mode: u32) -> Self {
+        self.nodes
+            .borrow_mut()
+            .insert(path.into(), Node::File { mode, contents: String::new() });
+        self
+    }
+
+    /// Register a plain file at `path` with the default mode and given text `contents`.
+    pub(crate) fn with_file_contents(self, path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        self.nodes
+            .borrow_mut()
+            .insert(path.into(), Node::File { mode: DEFAULT_FILE_MODE, contents: contents.into() });
+        self
+    }
+
+    /// Register a directory at `path`.
+    pub(crate) fn with_dir(self, path: impl Into<PathBuf>) -> Self {
+        self.nodes.borrow_mut().insert(path.into(), Node::Dir);
+        self
+    }
+
+    /// Register a symlink at `path` pointing to `target`.
+    pub(crate) fn with_symlink(self, path: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+        self.nodes.borrow_mut().insert(path.into(), Node::Symlink(target.into()));
+        self
+    }
+
+    fn node(&self, path: &Path) -> Option<Node> {
+        self.nodes.borrow().get(path).cloned()
+    }
+
+    /// Follow `path` through any symlink chain and return the node it
+    /// ultimately resolves to, mirroring how `Path::exists`/`Path::is_dir`
+    /// follow symlinks on a real filesystem.
+    fn resolve(&self, path: &Path) -> Option<Node> {
+        match self.node(path)? {
+            Node::Symlink(target) => {
+                let target = if target.is_absolute() {
+                    target
+                } else {
+                    path.parent()?.join(target)
+                };
+                self.resolve(&target)
+            }
+            node => Some(node),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Fs for FakeFs {
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.resolve(path), Some(Node::Dir))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<DirEntry>> {
+        if !self.is_dir(path) {
+            return Err(anyhow!("Could not read {:?}", path));
+        }
+
+        Ok(self
+            .nodes
+            .borrow()
+            .keys()
+            .filter(|candidate| candidate.parent() == Some(path))
+            .map(|candidate| DirEntry { path: candidate.clone() })
+            .collect())
+    }
+
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        match self.node(path) {
+            Some(Node::Symlink(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+        match self.node(path) {
+            Some(Node::Symlink(target)) => {
+                let target = if target.is_absolute() {
+                    target
+                } else {
+                    path.parent().expect("path to have a parent").join(target)
+                };
+                self.canonicalize(&target)
+            }
+            Some(_) => Ok(path.to_path_buf()),
+            None => Err(anyhow!("{:?} not found", path)),
+        }
+    }
+
+    fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+        self.nodes.borrow_mut().insert(link.to_path_buf(), Node::Symlink(original.to_path_buf()));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        // A directory rename on a real filesystem moves the whole subtree in
+        // one step (it's just a directory entry, not a recursive copy), so
+        // carry every descendant of `from` along to its new home under `to`.
+        let moved: Vec<(PathBuf, Node)> = {
+            let nodes = self.nodes.borrow();
+            if !nodes.contains_key(from) {
+                return Err(anyhow!("{:?} not found", from));
+            }
+
+            // Mirror the real `rename(2)` type check: a directory can only
+            // replace another directory, and a non-directory can only
+            // replace another non-directory (ENOTDIR/EISDIR otherwise).
+            if let Some(existing) = nodes.get(to) {
+                let from_is_dir = matches!(nodes.get(from), Some(Node::Dir));
+                let to_is_dir = matches!(existing, Node::Dir);
+                if from_is_dir != to_is_dir {
+                    return Err(anyhow!(
+                        "cannot rename {:?} to {:?}: directory/non-directory mismatch",
+                        from,
+                        to
+                    ));
+                }
+            }
+
+            nodes
+                .iter()
+                .filter(|(candidate, _)| candidate.starts_with(from))
+                .map(|(candidate, node)| (candidate.clone(), node.clone()))
+                .collect()
+        };
+
+        let mut nodes = self.nodes.borrow_mut();
+        for (candidate, node) in moved {
+            let relative = candidate.strip_prefix(from).expect("candidate to be under from");
+            nodes.remove(&candidate);
+            nodes.insert(to.join(relative), node);
+        }
+
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        let mut nodes = self.nodes.borrow_mut();
+        let children: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|candidate| candidate.starts_with(path))
+            .cloned()
+            .collect();
+
+        for child in children {
+            nodes.remove(&child);
+        }
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        match self.nodes.borrow_mut().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(anyhow!("{:?} not found", path)),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        self.nodes.borrow_mut().insert(path.to_path_buf(), Node::Dir);
+        Ok(())
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+        match self.resolve(from) {
+            Some(node @ Node::File { .. }) => {
+                self.nodes.borrow_mut().insert(to.to_path_buf(), node);
+                Ok(())
+            }
+            _ => Err(anyhow!("{:?} not found", from)),
+        }
+    }
+
+    fn mode(&self, path: &Path) -> Result<u32> {
+        match self.resolve(path) {
+            Some(Node::File { mode, .. }) => Ok(mode),
+            _ => Err(anyhow!("{:?} not found", path)),
+        }
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        match self.node(path) {
+            Some(Node::File { contents, .. }) => {
+                self.nodes.borrow_mut().insert(path.to_path_buf(), Node::File { mode, contents });
+                Ok(())
+            }
+            _ => Err(anyhow!("{:?} not found", path)),
+        }
+    }
+
+    fn read_to_string(&self, path: &Path) -> Result<String> {
+        match self.resolve(path) {
+            Some(Node::File { contents, .. }) => Ok(contents),
+            _ => Err(anyhow!("{:?} not found", path)),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! `.homerignore` glob filtering, so repo metadata and other unwanted paths
+//! never make it into the link plan. Patterns follow a small subset of
+//! gitignore syntax: a pattern with no `/` matches its basename at any depth
+//! under the input root, one with a `/` is anchored to the root itself.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::fs::Fs;
+
+/// Patterns ignored even without a `.homerignore` file or `--ignore` flag:
+/// the ignore file itself and the common VCS directories.
+const DEFAULT_PATTERNS: &[&str] = &[".homerignore", ".git", ".hg", ".svn"];
+
+/// Compiled set of ignore patterns, matched against paths relative to the
+/// input root. Build one with `Ignore::build`, then check entries against it
+/// during `Plan::new`'s recursion.
+pub(crate) struct Ignore {
+    set: GlobSet,
+}
+
+impl Ignore {
+    /// Build the ignore set for `root`: `DEFAULT_PATTERNS`, the contents of
+    /// `root/.homerignore` (one glob per line, blank lines and `#` comments
+    /// skipped, if the file exists), and `extra` (patterns passed via the
+    /// repeatable `--ignore` flag).
+    pub(crate) fn build(root: &Path, extra: &[String], fs: &dyn Fs) -> Result<Ignore> {
+        let mut patterns: Vec<String> = DEFAULT_PATTERNS.iter().map(|pattern| pattern.to_string()).collect();
+
+        let homerignore = root.join(".homerignore");
+        if fs.exists(&homerignore) {
+            let contents = fs.read_to_string(&homerignore)?;
+            patterns.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+
+        patterns.extend(extra.iter().cloned());
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &patterns {
+            // A pattern with no `/` should match at any depth, mirroring
+            // gitignore's treatment of bare names.
+            let anchored = if pattern.contains('/') { pattern.clone() } else { format!("**/{pattern}") };
+            builder.add(Glob::new(&anchored).context(format!("invalid ignore pattern {pattern:?}"))?);
+        }
+
+        Ok(Ignore {
+            set: builder.build().context("failed to compile .homerignore patterns")?,
+        })
+    }
+
+    /// Whether `relative` (a path relative to the input root) matches any
+    /// ignore pattern.
+    pub(crate) fn matches(&self, relative: &Path) -> bool {
+        self.set.is_match(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fs::FakeFs;
+
+    #[test]
+    fn default_patterns_ignore_common_vcs_dirs_at_any_depth() {
+        let root: PathBuf = "/input".into();
+        let fs = FakeFs::new().with_dir("/input");
+        let ignore = Ignore::build(&root, &[], &fs).expect("default patterns to compile");
+
+        assert!(ignore.matches(&PathBuf::from(".git")), "top-level .git should be ignored");
+        assert!(ignore.matches(&PathBuf::from("sub/.git")), "bare patterns should match at any depth");
+        assert!(!ignore.matches(&PathBuf::from("gitconfig")), "unrelated files should not match");
+    }
+
+    #[test]
+    fn homerignore_patterns_skip_blank_lines_and_comments() {
+        let root: PathBuf = "/input".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file_contents("/input/.homerignore", "secret\n\n# a comment\n");
+        let ignore = Ignore::build(&root, &[], &fs).expect(".homerignore to compile");
+
+        assert!(ignore.matches(&PathBuf::from("secret")), "pattern from .homerignore should apply");
+        assert!(ignore.matches(&PathBuf::from("sub/secret")), "bare pattern should match at any depth");
+        assert!(
+            !ignore.matches(&PathBuf::from("# a comment")),
+            "comment and blank lines should not be compiled as patterns"
+        );
+    }
+
+    #[test]
+    fn cli_ignore_patterns_are_combined_with_homerignore() {
+        let root: PathBuf = "/input".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file_contents("/input/.homerignore", "secret\n");
+        let ignore = Ignore::build(&root, &["extra".to_string()], &fs).expect("patterns to compile");
+
+        assert!(ignore.matches(&PathBuf::from("secret")), "the .homerignore pattern should still apply");
+        assert!(ignore.matches(&PathBuf::from("extra")), "the --ignore pattern should also apply");
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_anchored_to_the_root() {
+        let root: PathBuf = "/input".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file_contents("/input/.homerignore", "sub/secret\n");
+        let ignore = Ignore::build(&root, &[], &fs).expect(".homerignore to compile");
+
+        assert!(ignore.matches(&PathBuf::from("sub/secret")), "anchored pattern should match at its exact path");
+        assert!(
+            !ignore.matches(&PathBuf::from("other/sub/secret")),
+            "anchored pattern should not match at other depths"
+        );
+    }
+
+    #[test]
+    fn missing_homerignore_file_is_not_an_error() {
+        let root: PathBuf = "/input".into();
+        let fs = FakeFs::new().with_dir("/input");
+
+        assert!(
+            Ignore::build(&root, &[], &fs).is_ok(),
+            "a missing .homerignore should fall back to just the defaults"
+        );
+    }
+}
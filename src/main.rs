@@ -1,11 +1,20 @@
-use std::path::PathBuf;
+use std::fs as std_fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::{fs, io, os::unix};
 
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use crossterm::execute;
 use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use rand::Rng;
+
+mod fs;
+mod ignore;
+mod variant;
+
+use fs::{Fs, RealFs};
+use ignore::Ignore;
 
 /// "Doh!" A CLI for managing your dotfiles!
 #[derive(Parser, Debug)]
@@ -15,12 +24,42 @@ struct Args {
     #[arg(short, long)]
     force: bool,
 
+    /// Backup method for files that would block symlink creation, mirrors GNU
+    /// `install`'s `--backup[=CONTROL]`. Accepts `none`/`off`, `simple`/`never`,
+    /// `numbered`/`t` or `existing`/`nil`. Passing the flag without a value
+    /// behaves like `existing`.
+    #[arg(long, num_args = 0..=1, default_missing_value = "existing", default_value = "existing")]
+    backup: BackupMode,
+
     /// Disable backup, an action plan will be created, when other files block
     /// symlink creation they will be deleted instead of moved to a safe backup
-    /// location.
-    #[arg(long)]
+    /// location. Equivalent to `--backup=none`.
+    #[arg(long, conflicts_with = "backup")]
     no_backup: bool,
 
+    /// Suffix appended to backup files in `simple` mode, and used as a fallback
+    /// by `existing` mode when no numbered backup exists yet.
+    #[arg(long, default_value = "~")]
+    suffix: String,
+
+    /// Copy files into place instead of symlinking them. Useful for machines
+    /// where symlinks into the repo are undesirable (containers, deployed
+    /// servers, filesystems without symlink support).
+    #[arg(long)]
+    copy: bool,
+
+    /// Permission mode (octal, e.g. `644`) applied to files installed with
+    /// `--copy`, analogous to `install(1)`'s `--mode`. Ignored without
+    /// `--copy`; defaults to the source file's own mode.
+    #[arg(long, value_parser = parse_octal_mode)]
+    mode: Option<u32>,
+
+    /// Glob pattern to exclude from the link plan, relative to the input
+    /// directory. Repeatable. Combined with any patterns found in the input
+    /// directory's `.homerignore` file.
+    #[arg(long = "ignore")]
+    ignore: Vec<String>,
+
     /// Directory containing scripts that will be run after the plan is completed.
     /// If force flag is passed, no confirmation prompt will be shown.
     #[arg(long)]
@@ -35,10 +74,65 @@ struct Args {
     output: PathBuf,
 }
 
+/// Backup control method applied to a file that blocks symlink creation,
+/// modeled after GNU `install`'s `--backup[=CONTROL]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackupMode {
+    /// Delete the blocking file, no backup is kept.
+    None,
+    /// Always back up by appending `suffix` to the file name.
+    Simple,
+    /// Always back up as `dest.~N~`, using the next free number.
+    Numbered,
+    /// Use the numbered form if a `dest.~1~` backup already exists, otherwise
+    /// fall back to `Simple`.
+    Existing,
+}
+
+/// Error returned when `--backup` is given a value that doesn't match one of
+/// the recognized control methods.
+#[derive(Debug)]
+struct InvalidBackupMode(String);
+
+impl std::fmt::Display for InvalidBackupMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid backup method {:?} (expected none/off, simple/never, numbered/t or existing/nil)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidBackupMode {}
+
+impl std::str::FromStr for BackupMode {
+    type Err = InvalidBackupMode;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" | "off" => Ok(BackupMode::None),
+            "simple" | "never" => Ok(BackupMode::Simple),
+            "numbered" | "t" => Ok(BackupMode::Numbered),
+            "existing" | "nil" => Ok(BackupMode::Existing),
+            other => Err(InvalidBackupMode(other.to_string())),
+        }
+    }
+}
+
+/// Parse a `--mode` value as octal digits, matching `install(1)`'s
+/// `--mode <MODE>`.
+fn parse_octal_mode(s: &str) -> std::result::Result<u32, String> {
+    u32::from_str_radix(s, 8).map_err(|_| format!("invalid mode {s:?} (expected octal digits, e.g. 644)"))
+}
+
 fn main() -> Result<()> {
     let opt = Args::parse();
+    let fs = RealFs;
 
-    run_linking(opt.input, opt.output, !opt.no_backup, opt.force)?;
+    let backup = if opt.no_backup { BackupMode::None } else { opt.backup };
+    let options = LinkOptions { backup, suffix: &opt.suffix, copy: opt.copy, mode: opt.mode };
+    run_linking(opt.input, opt.output, options, &opt.ignore, opt.force, &fs)?;
 
     if let Some(scripts) = opt.scripts {
         run_scripts(scripts, opt.force)?;
@@ -52,20 +146,29 @@ fn main() -> Result<()> {
 /// `input` will be used to check for files and directories that will be linked
 /// into `output`. Both `input` and `output` should be valid directories.
 ///
-/// By passing the `backup` flag, files that would block symlink creation are moved
-/// to the same directory with a `bkp` extension, otherwise they will be deleted. The
-/// `force` flag disable user confirmation prompt by auto-accepting the plan.
-fn run_linking(input: PathBuf, output: PathBuf, backup: bool, force: bool) -> Result<()> {
+/// `options` controls backup handling, `--copy` mode and its permission
+/// override, see `LinkOptions`. `ignore` lists extra glob patterns to exclude
+/// from the plan, on top of the input directory's `.homerignore`. The `force`
+/// flag disables the user confirmation prompt by auto-accepting the plan.
+fn run_linking(
+    input: PathBuf,
+    output: PathBuf,
+    options: LinkOptions,
+    ignore: &[String],
+    force: bool,
+    fs: &dyn Fs,
+) -> Result<()> {
     let input = canonicalize_dir(input)?;
     let output = canonicalize_dir(output)?;
+    let ignore = Ignore::build(&input, ignore, fs)?;
 
-    let plan = Plan::new(&input, &output, backup)?;
+    let plan = Plan::new(&input, &output, options, &input, &ignore, fs)?;
     if plan.is_empty() {
         return Ok(());
     }
 
     // Show the plan to the user, this substitute a verbose option, as it's always shown.
-    plan.show()?;
+    plan.show(fs)?;
 
     if !force {
         // User was prompted, but did not accept the plan.
@@ -74,7 +177,7 @@ fn run_linking(input: PathBuf, output: PathBuf, backup: bool, force: bool) -> Re
         }
     }
 
-    plan.execute()?;
+    plan.execute(fs)?;
     Ok(())
 }
 
@@ -85,7 +188,7 @@ fn run_linking(input: PathBuf, output: PathBuf, backup: bool, force: bool) -> Re
 /// confirmation prompt by auto-accepting the plan.
 fn run_scripts(path: PathBuf, force: bool) -> Result<()> {
     let path = canonicalize_dir(path)?;
-    let entries = fs::read_dir(&path).context(format!("Could not read {:?}", &path))?;
+    let entries = std_fs::read_dir(&path).context(format!("Could not read {:?}", &path))?;
 
     // Get all files inside the scripts directory, but do not recurse further
     // into it's directories.
@@ -137,10 +240,10 @@ fn run_scripts(path: PathBuf, force: bool) -> Result<()> {
     Ok(())
 }
 
-/// Canonicalize a directory path by calling `fs::canonicalize` and failing if
-/// the result path is not a directory.
+/// Canonicalize a directory path by calling `std::fs::canonicalize` and
+/// failing if the result path is not a directory.
 fn canonicalize_dir(path: PathBuf) -> Result<PathBuf> {
-    let input = fs::canonicalize(&path).context(format!("{:?} not found", &path))?;
+    let input = std_fs::canonicalize(&path).context(format!("{:?} not found", &path))?;
 
     if !input.is_dir() {
         return Err(anyhow!(format!("{:?} is not a directory", path)));
@@ -167,6 +270,114 @@ fn prompt_user() -> Result<bool> {
     Ok(input.trim().to_lowercase() == "y")
 }
 
+/// Compute the concrete backup target for `dest` under the given `mode`,
+/// scanning `dest`'s parent directory when the numbered form is needed.
+///
+/// For `Numbered` (and `Existing` once a `dest.~1~` backup exists), this scans
+/// sibling entries matching `<name>.~(\d+)~` and uses one past the highest
+/// number found, so an existing backup is never overwritten.
+fn backup_target(dest: &Path, mode: BackupMode, suffix: &str, fs: &dyn Fs) -> Result<PathBuf> {
+    match mode {
+        BackupMode::None => Ok(dest.to_path_buf()),
+        BackupMode::Simple => Ok(simple_backup_path(dest, suffix)),
+        BackupMode::Numbered => next_numbered_backup_path(dest, fs),
+        BackupMode::Existing => {
+            if fs.exists(&numbered_backup_path(dest, 1)) {
+                next_numbered_backup_path(dest, fs)
+            } else {
+                Ok(simple_backup_path(dest, suffix))
+            }
+        }
+    }
+}
+
+/// Path for the `Simple` backup method: `dest` with `suffix` appended.
+fn simple_backup_path(dest: &Path, suffix: &str) -> PathBuf {
+    let mut name = dest.file_name().expect("dest to have a file name").to_os_string();
+    name.push(suffix);
+    dest.with_file_name(name)
+}
+
+/// Path for backup number `n` in the numbered scheme: `dest.~n~`.
+fn numbered_backup_path(dest: &Path, n: u64) -> PathBuf {
+    let name = dest.file_name().expect("dest to have a file name").to_string_lossy();
+    dest.with_file_name(format!("{name}.~{n}~"))
+}
+
+/// Build a temporary sibling path for `dest`, named `<dest>.<hex>.tmp` with a
+/// few random bytes hex-encoded into the suffix, so it's vanishingly
+/// unlikely to collide with a real file.
+fn temp_sibling_path(dest: &Path) -> PathBuf {
+    let suffix: String = rand::thread_rng().gen::<[u8; 4]>().iter().map(|byte| format!("{byte:02x}")).collect();
+
+    let mut name = dest.file_name().expect("dest to have a file name").to_os_string();
+    name.push(format!(".{suffix}.tmp"));
+    dest.with_file_name(name)
+}
+
+/// Materialize `path` at `dest`, used by `--copy` mode in place of
+/// `Fs::symlink`. Directories are recreated and recursed into; files are
+/// copied with `mode` applied if given, otherwise the source's own mode.
+fn copy_recursive(fs: &dyn Fs, path: &Path, dest: &Path, mode: Option<u32>) -> Result<()> {
+    if fs.is_dir(path) {
+        fs.create_dir(dest)?;
+
+        for entry in fs.read_dir(path)? {
+            copy_recursive(fs, &entry.path(), &dest.join(entry.file_name()), mode)?;
+        }
+
+        Ok(())
+    } else {
+        fs.copy_file(path, dest)?;
+
+        let mode = match mode {
+            Some(mode) => mode,
+            None => fs.mode(path)?,
+        };
+        fs.set_mode(dest, mode)
+    }
+}
+
+/// Scan `dest`'s parent directory for existing `<name>.~N~` backups and
+/// return the path for one past the highest `N` found (starting at 1).
+fn next_numbered_backup_path(dest: &Path, fs: &dyn Fs) -> Result<PathBuf> {
+    let name = dest.file_name().expect("dest to have a file name").to_string_lossy();
+    let pattern = regex::Regex::new(&format!(r"^{}\.~(\d+)~$", regex::escape(&name))).unwrap();
+
+    let parent = dest.parent().expect("dest to have a parent");
+    let mut max = 0u64;
+    if let Ok(entries) = fs.read_dir(parent) {
+        for entry in entries {
+            let entry_name = entry.file_name();
+            if let Some(captures) = pattern.captures(&entry_name.to_string_lossy()) {
+                if let Ok(n) = captures[1].parse::<u64>() {
+                    max = max.max(n);
+                }
+            }
+        }
+    }
+
+    Ok(numbered_backup_path(dest, max + 1))
+}
+
+/// Options controlling how a file or directory is linked into place, bundled
+/// together since `run_linking` and `Plan::new` both thread them through
+/// unchanged (`Plan::new` to every recursive call over its children) and
+/// passing them as separate positional arguments got easy to transpose as
+/// they grew.
+#[derive(Debug, Clone, Copy)]
+struct LinkOptions<'a> {
+    /// See `BackupMode`.
+    backup: BackupMode,
+    /// Suffix appended to backup files in `Simple` mode, see `Args::suffix`.
+    suffix: &'a str,
+    /// Materialize a real copy of `path` at `dest` instead of symlinking.
+    copy: bool,
+    /// Permission mode applied to copied files; `None` keeps the source's own
+    /// mode. Ignored unless `copy` is set.
+    mode: Option<u32>,
+}
+
 /// Action plan for linking files into a destination directory.
 /// There are two variants, `Plan::Noop` and `Plan::Link`.
 #[derive(Debug, PartialEq)]
@@ -180,16 +391,22 @@ enum Plan {
         children: Vec<Plan>,
     },
 
-    /// `Plan::Link` denotes a symlinking action, it can refer to a file or a
-    /// directory. The `replace` flag on it is set to `true` if there is already
-    /// an existing file or directory on the destination path, depending on the
-    /// value of the `backup` flag, this existing file should be moved to a safe
-    /// location or deleted from disk.
+    /// `Plan::Link` denotes a symlinking (or, with `copy` set, a materializing)
+    /// action, it can refer to a file or a directory. The `replace` flag on it
+    /// is set to `true` if there is already an existing file or directory on
+    /// the destination path, in which case `backup` decides whether (and how)
+    /// that file is preserved.
     Link {
         path: PathBuf,
         dest: PathBuf,
         replace: bool,
-        backup: bool,
+        backup: BackupMode,
+        suffix: String,
+        /// Materialize a real copy of `path` at `dest` instead of symlinking.
+        copy: bool,
+        /// Permission mode applied to copied files; `None` keeps the
+        /// source's own mode. Ignored unless `copy` is set.
+        mode: Option<u32>,
     },
 }
 
@@ -197,29 +414,50 @@ impl Plan {
     /// Create an action plan based on a input `path` and a destination. This will
     /// recurse inside the provided directories for other directories and files to
     /// be added to the action plan.
-    fn new(path: &PathBuf, dest: &PathBuf, backup: bool) -> Result<Plan> {
-        if !path.exists() {
+    ///
+    /// `options` controls backup handling, `--copy` mode and its permission
+    /// override, see `LinkOptions`. `root` is the top-level input directory,
+    /// used to resolve entries against `ignore` regardless of how deep the
+    /// recursion currently is. `fs` is the filesystem the plan is built
+    /// against, `RealFs` in production and `FakeFs` in tests.
+    fn new(
+        path: &PathBuf,
+        dest: &PathBuf,
+        options: LinkOptions,
+        root: &Path,
+        ignore: &Ignore,
+        fs: &dyn Fs,
+    ) -> Result<Plan> {
+        if !fs.exists(path) {
             anyhow::bail!("{:?} does not exist", path);
         }
 
-        let dest_exists_or_is_link = dest.exists() || std::fs::read_link(dest).is_ok();
+        let dest_exists_or_is_link = fs.exists(dest) || fs.read_link(dest).is_some();
 
         // When the current path denotes a directory, we should recurse into
         // it's entries and add them to the action plan accordingly.
         let mut children = Vec::new();
-        if path.is_dir() {
-            let entries: Vec<_> = fs::read_dir(path)
-                .context(format!("Could not read {:?}", path))?
+        if fs.is_dir(path) {
+            let entries: Vec<_> = fs
+                .read_dir(path)?
+                .into_iter()
+                .filter(|entry| {
+                    let entry_path = entry.path();
+                    let relative = entry_path.strip_prefix(root).unwrap_or(&entry_path);
+                    !ignore.matches(relative)
+                })
                 .collect();
 
-            for entry in entries {
-                let entry = entry?;
-                let dest = dest.join(entry.path().strip_prefix(path)?);
+            // Group entries sharing a `##host.`/`##os.` tagged base name and
+            // keep only the variant matching this machine, so e.g. only one
+            // of `gitconfig##host.laptop` and `gitconfig##os.linux` is linked.
+            for (entry, dest_name) in variant::resolve(entries) {
+                let dest = dest.join(dest_name);
 
-                children.push(Plan::new(&entry.path(), &dest, backup)?);
+                children.push(Plan::new(&entry.path(), &dest, options, root, ignore, fs)?);
             }
 
-            if dest.is_dir() {
+            if fs.is_dir(dest) {
                 return Ok(Plan::Noop {
                     path: path.into(),
                     dest: dest.into(),
@@ -229,31 +467,36 @@ impl Plan {
                 return Ok(Plan::Link {
                     path: path.into(),
                     dest: dest.into(),
-                    backup,
+                    backup: options.backup,
+                    suffix: options.suffix.to_string(),
                     replace: dest_exists_or_is_link,
+                    copy: options.copy,
+                    mode: options.mode,
                 });
             }
         }
 
         // At this point we know that dest is a file and should have a parent.
         let mut dest_parent = dest.parent().expect("dest to have a parent").to_path_buf();
-        let canonicalized_dest = match std::fs::read_link(dest) {
-            Ok(dest) => {
+        let canonicalized_dest = match fs.read_link(dest) {
+            Some(dest) => {
                 if dest.is_absolute() {
-                    dest.canonicalize().ok()
+                    fs.canonicalize(&dest).ok()
                 } else {
                     dest_parent.push(dest);
-                    dest_parent.canonicalize().ok()
+                    fs.canonicalize(&dest_parent).ok()
                 }
             }
-            Err(_) => None,
+            None => None,
         };
 
-        let canonicalized_path = path
-            .canonicalize()
+        let canonicalized_path = fs
+            .canonicalize(path)
             .context(format!("failed to canonicalize {path:?}"))?;
 
-        if canonicalized_dest.is_some() && canonicalized_dest.unwrap() == canonicalized_path {
+        // In `copy` mode `dest` is a real file, never a symlink to `path`, so
+        // this idempotency check doesn't apply and we always (re-)materialize.
+        if !options.copy && canonicalized_dest.is_some() && canonicalized_dest.unwrap() == canonicalized_path {
             Ok(Plan::Noop {
                 path: path.into(),
                 dest: dest.into(),
@@ -261,10 +504,13 @@ impl Plan {
             })
         } else {
             Ok(Plan::Link {
-                backup,
+                backup: options.backup,
+                suffix: options.suffix.to_string(),
                 replace: dest_exists_or_is_link,
                 path: path.into(),
                 dest: dest.into(),
+                copy: options.copy,
+                mode: options.mode,
             })
         }
     }
@@ -278,48 +524,96 @@ impl Plan {
         }
     }
 
-    /// Execute the current plan.
-    /// This will modify the disk. This function is unix-only.
+    /// Execute the current plan against `fs`.
+    /// This will modify the disk when `fs` is `RealFs`.
     ///
     /// When dealing with `Plan::Link`, we need to be careful about replacing blocking
     /// files in the destination directory, they can be backed-up to a safe location or
     /// deleted from dist. This will recurse and call `Plan::execute` on plan's children.
-    fn execute(&self) -> Result<()> {
+    ///
+    /// The new symlink is always built at a temporary sibling path first, so a
+    /// failure while building it never touches `dest`. Landing it at `dest` is
+    /// only truly atomic, though, for the no-backup, non-directory case: a
+    /// single `fs::rename` of `temp` over `dest`, which on Unix always
+    /// resolves `dest` to either the old or the new target, never nothing.
+    /// Every other case (a kept backup, or a directory `dest`/`temp` needing
+    /// the stash-aside dance) takes *two* renames with a gap in between where
+    /// `dest` has already been moved aside but the replacement hasn't landed
+    /// yet — a crash or I/O error in that window leaves `dest` missing, with
+    /// the old content sitting at the backup target or stash path instead.
+    fn execute(&self, fs: &dyn Fs) -> Result<()> {
         match self {
             Plan::Link {
                 path,
                 dest,
                 replace,
                 backup,
+                suffix,
+                copy,
+                mode,
             } => {
-                if *replace && *backup {
-                    fs::rename(dest, dest.with_extension("bkp"))?;
-                } else if *replace {
-                    if dest.is_dir() {
-                        fs::remove_dir_all(dest)?;
-                    } else {
-                        fs::remove_file(dest)?;
+                let temp = temp_sibling_path(dest);
+                if *copy {
+                    copy_recursive(fs, path, &temp, *mode)?;
+                } else {
+                    fs.symlink(path, &temp)?;
+                }
+
+                if *replace {
+                    if *backup == BackupMode::None {
+                        // A non-directory dest (file or symlink) can be replaced by a
+                        // single rename, which is atomic on Unix: dest always resolves
+                        // to either the old or the new target, never nothing. This only
+                        // works when `temp` is also a non-directory though (a `--copy`
+                        // of a directory `path` materializes `temp` as a real directory),
+                        // since `rename` refuses to swap a directory for a non-directory
+                        // or vice-versa (ENOTDIR/EISDIR). Either a directory dest or a
+                        // directory temp falls back to the stash-aside dance.
+                        let dest_is_dir = fs.is_dir(dest) && fs.read_link(dest).is_none();
+                        let temp_is_dir = *copy && fs.is_dir(path);
+                        if !dest_is_dir && !temp_is_dir {
+                            fs.rename(&temp, dest)?;
+                            return Ok(());
+                        }
+
+                        let stash = temp_sibling_path(dest);
+                        fs.rename(dest, &stash)?;
+                        fs.rename(&temp, dest)?;
+                        if dest_is_dir {
+                            fs.remove_dir_all(&stash)?;
+                        } else {
+                            fs.remove_file(&stash)?;
+                        }
+
+                        return Ok(());
                     }
+
+                    let target = backup_target(dest, *backup, suffix, fs)?;
+                    fs.rename(dest, &target)?;
                 }
 
-                // NOTE: This makes the binary unix-only ¯\_(ツ)_/¯.
-                unix::fs::symlink(path, dest)?;
+                fs.rename(&temp, dest)?;
                 Ok(())
             }
-            Plan::Noop { children, .. } => children.iter().try_for_each(Plan::execute),
+            Plan::Noop { children, .. } => children.iter().try_for_each(|child| child.execute(fs)),
         }
     }
 
     /// Show the plan, recursing and displaying all it's children aswell.
-    fn show(&self) -> Result<()> {
+    fn show(&self, fs: &dyn Fs) -> Result<()> {
         match self {
             Plan::Link {
                 path,
                 dest,
                 replace,
                 backup,
+                suffix,
+                copy,
+                ..
             } => {
-                if *replace && *backup {
+                if *replace && *backup != BackupMode::None {
+                    let target = backup_target(dest, *backup, suffix, fs)?;
+
                     // Show backup formatted text
                     execute!(
                         io::stdout(),
@@ -328,11 +622,7 @@ impl Plan {
                         Print("~ mv: "),
                         SetAttribute(Attribute::Reset),
                         SetForegroundColor(Color::Magenta),
-                        Print(format!(
-                            "{} -> {}",
-                            dest.display(),
-                            dest.with_extension("bkp").display()
-                        )),
+                        Print(format!("{} -> {}", dest.display(), target.display())),
                         Print("\n"),
                         SetForegroundColor(Color::Reset),
                     )?;
@@ -348,7 +638,7 @@ impl Plan {
                         Print(format!("{}", dest.display())),
                     )?;
 
-                    if dest.is_dir() {
+                    if fs.is_dir(dest) {
                         execute!(
                             io::stdout(),
                             SetForegroundColor(Color::Red),
@@ -363,12 +653,12 @@ impl Plan {
                     execute!(io::stdout(), Print("\n"), SetForegroundColor(Color::Reset))?;
                 }
 
-                // Show link formatted text
+                // Show link (or, with `--copy`, copy) formatted text
                 execute!(
                     io::stdout(),
                     SetForegroundColor(Color::Cyan),
                     SetAttribute(Attribute::Bold),
-                    Print("~ ln: "),
+                    Print(if *copy { "~ cp: " } else { "~ ln: " }),
                     SetAttribute(Attribute::Reset),
                     SetForegroundColor(Color::Cyan),
                     Print(format!("{} -> {}", dest.display(), path.display())),
@@ -378,30 +668,53 @@ impl Plan {
 
                 Ok(())
             }
-            Plan::Noop { children, .. } => children.iter().try_for_each(Plan::show),
+            Plan::Noop { children, .. } => children.iter().try_for_each(|child| child.show(fs)),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use super::*;
+    use crate::fs::FakeFs;
+
+    const NO_BACKUP: BackupMode = BackupMode::None;
+
+    /// Options for `Plan::new` calls that don't exercise backup or `--copy`
+    /// behavior, which is most of them.
+    const NO_COPY_OPTIONS: LinkOptions<'static> = LinkOptions {
+        backup: NO_BACKUP,
+        suffix: "~",
+        copy: false,
+        mode: None,
+    };
+
+    /// An `Ignore` that excludes nothing beyond `Ignore::build`'s defaults,
+    /// for tests that don't exercise `.homerignore`/`--ignore` filtering.
+    fn no_ignore(root: &Path, fs: &dyn Fs) -> Ignore {
+        Ignore::build(root, &[], fs).expect("default ignore patterns to compile")
+    }
 
     #[test]
     fn missing_input() {
-        let plan = Plan::new(
-            &"./testdata/life".into(),
-            &"./testdata/output".into(),
-            false,
-        );
+        let fs = FakeFs::new().with_dir("/output");
+
+        let root: PathBuf = "/input".into();
+        let ignore = no_ignore(&root, &fs);
+        let plan = Plan::new(&root, &"/output".into(), NO_COPY_OPTIONS, &root, &ignore, &fs);
         assert!(plan.is_err(), "input path should not exist");
     }
 
     #[test]
     fn missing_output() {
-        let path: PathBuf = "./testdata/simple".into();
-        let dest: PathBuf = "./testdata/life".into();
-        let plan = Plan::new(&path, &dest, false);
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/missing".into();
+        let fs = FakeFs::new().with_dir("/input");
+
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
 
         match plan {
             Ok(Plan::Link {
@@ -409,10 +722,11 @@ mod tests {
                 dest: d,
                 backup,
                 replace,
+                ..
             }) => {
                 assert_eq!(p, path, "the path should be the same as input");
                 assert_eq!(d, dest, "the desitnation should be the new folder");
-                assert!(!backup, "the input asked for no backup");
+                assert_eq!(backup, NO_BACKUP, "the input asked for no backup");
                 assert!(!replace, "should not replace something that does not exist");
             }
             _ => panic!("plan should be to link folder"),
@@ -421,8 +735,12 @@ mod tests {
 
     #[test]
     fn simple() {
-        let path: PathBuf = "./testdata/simple".into();
-        let dest: PathBuf = "./testdata/output".into();
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file("/input/file")
+            .with_dir("/output");
 
         let expected = Plan::Noop {
             path: path.clone(),
@@ -430,12 +748,16 @@ mod tests {
             children: vec![Plan::Link {
                 path: path.join("file"),
                 dest: dest.join("file"),
-                backup: false,
+                backup: NO_BACKUP,
+                suffix: "~".into(),
                 replace: false,
+                copy: false,
+                mode: None,
             }],
         };
 
-        let plan = Plan::new(&path, &dest, false);
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
         assert!(plan.is_ok(), "everything should be fine");
         assert_eq!(
             plan.unwrap(),
@@ -446,8 +768,13 @@ mod tests {
 
     #[test]
     fn idempotent() {
-        let path: PathBuf = "./testdata/idempotent".into();
-        let dest: PathBuf = "./testdata/output".into();
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_dir("/input/linked")
+            .with_dir("/output")
+            .with_symlink("/output/linked", "/input/linked");
 
         let expected = Plan::Noop {
             path: path.clone(),
@@ -459,7 +786,8 @@ mod tests {
             }],
         };
 
-        let plan = Plan::new(&path, &dest, false);
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
         assert!(plan.is_ok(), "everything should be fine");
         assert_eq!(
             plan.unwrap(),
@@ -470,8 +798,13 @@ mod tests {
 
     #[test]
     fn replace() {
-        let path: PathBuf = "./testdata/replace".into();
-        let dest: PathBuf = "./testdata/output".into();
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file("/input/replaceable")
+            .with_dir("/output")
+            .with_file("/output/replaceable");
 
         let expected = Plan::Noop {
             path: path.clone(),
@@ -479,12 +812,16 @@ mod tests {
             children: vec![Plan::Link {
                 path: path.join("replaceable"),
                 dest: dest.join("replaceable"),
-                backup: false,
+                backup: NO_BACKUP,
+                suffix: "~".into(),
                 replace: true,
+                copy: false,
+                mode: None,
             }],
         };
 
-        let plan = Plan::new(&path, &dest, false);
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
         assert!(plan.is_ok(), "everything should be fine");
         assert_eq!(
             plan.unwrap(),
@@ -495,8 +832,13 @@ mod tests {
 
     #[test]
     fn not_folder() {
-        let path: PathBuf = "./testdata/not_folder".into();
-        let dest: PathBuf = "./testdata/output".into();
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file("/input/not_folder")
+            .with_dir("/output")
+            .with_dir("/output/not_folder");
 
         let expected = Plan::Noop {
             path: path.clone(),
@@ -504,12 +846,16 @@ mod tests {
             children: vec![Plan::Link {
                 path: path.join("not_folder"),
                 dest: dest.join("not_folder"),
-                backup: false,
+                backup: NO_BACKUP,
+                suffix: "~".into(),
                 replace: true,
+                copy: false,
+                mode: None,
             }],
         };
 
-        let plan = Plan::new(&path, &dest, false);
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
         assert!(plan.is_ok(), "everything should be fine");
         assert_eq!(
             plan.unwrap(),
@@ -520,8 +866,15 @@ mod tests {
 
     #[test]
     fn different_link() {
-        let path: PathBuf = "./testdata/different_link".into();
-        let dest: PathBuf = "./testdata/output".into();
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file("/input/different_link")
+            .with_file("/input/different_link_broken")
+            .with_dir("/output")
+            .with_symlink("/output/different_link", "/input/other")
+            .with_symlink("/output/different_link_broken", "/input/missing");
 
         let expected = Plan::Noop {
             path: path.clone(),
@@ -530,19 +883,26 @@ mod tests {
                 Plan::Link {
                     path: path.join("different_link"),
                     dest: dest.join("different_link"),
-                    backup: false,
+                    backup: NO_BACKUP,
+                    suffix: "~".into(),
                     replace: true,
+                    copy: false,
+                    mode: None,
                 },
                 Plan::Link {
                     path: path.join("different_link_broken"),
                     dest: dest.join("different_link_broken"),
-                    backup: false,
+                    backup: NO_BACKUP,
+                    suffix: "~".into(),
                     replace: true,
+                    copy: false,
+                    mode: None,
                 },
             ],
         };
 
-        let plan = Plan::new(&path, &dest, false);
+        let ignore = no_ignore(&path, &fs);
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
         println!("{:?}", plan);
         assert!(plan.is_ok(), "everything should be fine");
         assert_eq!(
@@ -551,4 +911,387 @@ mod tests {
             "the output should be repalced in both cases as it does not link to the input"
         );
     }
+
+    #[test]
+    fn ignores_homerignore_and_cli_patterns() {
+        let path: PathBuf = "/input".into();
+        let dest: PathBuf = "/output".into();
+        let fs = FakeFs::new()
+            .with_dir("/input")
+            .with_file_contents("/input/.homerignore", "README.md\n# a comment\n\n*.bkp\n")
+            .with_file("/input/README.md")
+            .with_file("/input/file.bkp")
+            .with_file("/input/file")
+            .with_dir("/input/sub")
+            .with_file("/input/sub/secret")
+            .with_dir("/output")
+            .with_dir("/output/sub");
+
+        let expected = Plan::Noop {
+            path: path.clone(),
+            dest: dest.clone(),
+            children: vec![
+                Plan::Link {
+                    path: path.join("file"),
+                    dest: dest.join("file"),
+                    backup: NO_BACKUP,
+                    suffix: "~".into(),
+                    replace: false,
+                    copy: false,
+                    mode: None,
+                },
+                Plan::Noop {
+                    path: path.join("sub"),
+                    dest: dest.join("sub"),
+                    children: vec![],
+                },
+            ],
+        };
+
+        let ignore = Ignore::build(&path, &["sub/secret".to_string()], &fs).unwrap();
+        let plan = Plan::new(&path, &dest, NO_COPY_OPTIONS, &path, &ignore, &fs);
+        assert!(plan.is_ok(), "everything should be fine");
+        assert_eq!(
+            plan.unwrap(),
+            expected,
+            "the ignored entries should not be part of the plan, only plain `file` survives"
+        );
+    }
+
+    #[test]
+    fn execute_replaces_blocking_file_with_symlink() {
+        let path: PathBuf = "/input/file".into();
+        let dest: PathBuf = "/output/file".into();
+        let fs = FakeFs::new().with_file("/input/file").with_file("/output/file");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: NO_BACKUP,
+            suffix: "~".into(),
+            replace: true,
+            copy: false,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+        assert_eq!(
+            fs.read_link(&dest),
+            Some(path),
+            "dest should now be a symlink to path"
+        );
+    }
+
+    #[test]
+    fn execute_replaces_blocking_directory_with_symlink() {
+        let path: PathBuf = "/input/dir".into();
+        let dest: PathBuf = "/output/dir".into();
+        let fs = FakeFs::new()
+            .with_dir("/input/dir")
+            .with_dir("/output/dir")
+            .with_file("/output/dir/child");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: NO_BACKUP,
+            suffix: "~".into(),
+            replace: true,
+            copy: false,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+        assert_eq!(
+            fs.read_link(&dest),
+            Some(path),
+            "dest should now be a symlink to path"
+        );
+        assert!(!fs.exists(&PathBuf::from("/output/dir/child")), "old directory contents should be gone");
+    }
+
+    #[test]
+    fn execute_backs_up_blocking_file_before_symlinking() {
+        let path: PathBuf = "/input/file".into();
+        let dest: PathBuf = "/output/file".into();
+        let fs = FakeFs::new().with_file("/input/file").with_file("/output/file");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: BackupMode::Simple,
+            suffix: "~".into(),
+            replace: true,
+            copy: false,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+        assert!(fs.exists(&PathBuf::from("/output/file~")), "blocking file should be backed up");
+        assert_eq!(fs.read_link(&dest), Some(path), "dest should now be a symlink to path");
+    }
+
+    /// `Fs` wrapper that records every `rename` call it forwards to `inner`,
+    /// so tests can assert on the order `Plan::execute` performs them in. Used
+    /// to show the backup path is genuinely two separate renames, not one
+    /// atomic swap — see the gap called out on `Plan::execute`'s doc comment.
+    struct RenameOrderFs<'a> {
+        inner: &'a FakeFs,
+        renames: RefCell<Vec<(PathBuf, PathBuf)>>,
+    }
+
+    impl<'a> RenameOrderFs<'a> {
+        fn new(inner: &'a FakeFs) -> Self {
+            Self { inner, renames: RefCell::new(Vec::new()) }
+        }
+    }
+
+    impl Fs for RenameOrderFs<'_> {
+        fn exists(&self, path: &Path) -> bool {
+            self.inner.exists(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.inner.is_dir(path)
+        }
+
+        fn read_dir(&self, path: &Path) -> Result<Vec<fs::DirEntry>> {
+            self.inner.read_dir(path)
+        }
+
+        fn read_link(&self, path: &Path) -> Option<PathBuf> {
+            self.inner.read_link(path)
+        }
+
+        fn canonicalize(&self, path: &Path) -> Result<PathBuf> {
+            self.inner.canonicalize(path)
+        }
+
+        fn symlink(&self, original: &Path, link: &Path) -> Result<()> {
+            self.inner.symlink(original, link)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.renames.borrow_mut().push((from.to_path_buf(), to.to_path_buf()));
+            self.inner.rename(from, to)
+        }
+
+        fn remove_dir_all(&self, path: &Path) -> Result<()> {
+            self.inner.remove_dir_all(path)
+        }
+
+        fn remove_file(&self, path: &Path) -> Result<()> {
+            self.inner.remove_file(path)
+        }
+
+        fn create_dir(&self, path: &Path) -> Result<()> {
+            self.inner.create_dir(path)
+        }
+
+        fn copy_file(&self, from: &Path, to: &Path) -> Result<()> {
+            self.inner.copy_file(from, to)
+        }
+
+        fn mode(&self, path: &Path) -> Result<u32> {
+            self.inner.mode(path)
+        }
+
+        fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+            self.inner.set_mode(path, mode)
+        }
+
+        fn read_to_string(&self, path: &Path) -> Result<String> {
+            self.inner.read_to_string(path)
+        }
+    }
+
+    #[test]
+    fn execute_backup_path_is_not_a_single_atomic_rename() {
+        let path: PathBuf = "/input/file".into();
+        let dest: PathBuf = "/output/file".into();
+        let target: PathBuf = "/output/file~".into();
+        let fake = FakeFs::new().with_file("/input/file").with_file("/output/file");
+        let fs = RenameOrderFs::new(&fake);
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: BackupMode::Simple,
+            suffix: "~".into(),
+            replace: true,
+            copy: false,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+
+        let renames = fs.renames.borrow();
+        assert_eq!(
+            renames.len(),
+            2,
+            "the backup path takes two renames, not one atomic swap, so there's a window where dest exists at neither the old nor the new target"
+        );
+        assert_eq!(renames[0], (dest.clone(), target), "dest is moved aside to the backup target first");
+        assert_eq!(renames[1].1, dest, "the new symlink is only landed at dest as a second, separate rename");
+    }
+
+    #[test]
+    fn execute_copies_file_preserving_mode() {
+        let path: PathBuf = "/input/file".into();
+        let dest: PathBuf = "/output/file".into();
+        let fs = FakeFs::new().with_file_mode("/input/file", 0o755).with_dir("/output");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: NO_BACKUP,
+            suffix: "~".into(),
+            replace: false,
+            copy: true,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+        assert_eq!(fs.read_link(&dest), None, "dest should be a real file, not a symlink");
+        assert_eq!(fs.mode(&dest).unwrap(), 0o755, "dest should keep the source's mode");
+    }
+
+    #[test]
+    fn execute_copies_file_with_mode_override() {
+        let path: PathBuf = "/input/file".into();
+        let dest: PathBuf = "/output/file".into();
+        let fs = FakeFs::new().with_file_mode("/input/file", 0o755).with_dir("/output");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: NO_BACKUP,
+            suffix: "~".into(),
+            replace: false,
+            copy: true,
+            mode: Some(0o644),
+        };
+
+        plan.execute(&fs).expect("execute should succeed");
+        assert_eq!(fs.mode(&dest).unwrap(), 0o644, "dest should use the overridden mode");
+    }
+
+    #[test]
+    fn execute_copies_directory_over_blocking_file() {
+        let path: PathBuf = "/input/dir".into();
+        let dest: PathBuf = "/output/dir".into();
+        let fs = FakeFs::new()
+            .with_dir("/input/dir")
+            .with_file("/input/dir/child")
+            .with_file("/output/dir");
+
+        let plan = Plan::Link {
+            path: path.clone(),
+            dest: dest.clone(),
+            backup: NO_BACKUP,
+            suffix: "~".into(),
+            replace: true,
+            copy: true,
+            mode: None,
+        };
+
+        plan.execute(&fs).expect("execute should succeed, falling back off the single-rename fast path");
+        assert!(fs.is_dir(&dest), "dest should now be a real directory, not the old blocking file");
+        assert!(
+            fs.exists(&PathBuf::from("/output/dir/child")),
+            "the copied directory's contents should have landed at dest"
+        );
+    }
+
+    #[test]
+    fn numbered_backup_path_formats_the_given_number() {
+        let dest: PathBuf = "/output/replaceable".into();
+        assert_eq!(numbered_backup_path(&dest, 1), PathBuf::from("/output/replaceable.~1~"));
+    }
+
+    #[test]
+    fn simple_backup_path_appends_suffix() {
+        let dest: PathBuf = "/output/replaceable".into();
+        assert_eq!(simple_backup_path(&dest, "~"), PathBuf::from("/output/replaceable~"));
+    }
+
+    #[test]
+    fn next_numbered_backup_path_starts_at_one_with_no_existing_siblings() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new().with_file("/output/replaceable");
+
+        let next = next_numbered_backup_path(&dest, &fs).expect("scan should succeed");
+        assert_eq!(next, PathBuf::from("/output/replaceable.~1~"));
+    }
+
+    #[test]
+    fn next_numbered_backup_path_picks_one_past_the_highest_existing_number() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new()
+            .with_dir("/output")
+            .with_file("/output/replaceable")
+            .with_file("/output/replaceable.~1~")
+            .with_file("/output/replaceable.~3~");
+
+        let next = next_numbered_backup_path(&dest, &fs).expect("scan should succeed");
+        assert_eq!(
+            next,
+            PathBuf::from("/output/replaceable.~4~"),
+            "next number should be one past the highest found, gaps notwithstanding"
+        );
+    }
+
+    #[test]
+    fn next_numbered_backup_path_ignores_siblings_with_a_different_name() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new()
+            .with_dir("/output")
+            .with_file("/output/replaceable")
+            .with_file("/output/replaceable.~5~")
+            .with_file("/output/other.~9~");
+
+        let next = next_numbered_backup_path(&dest, &fs).expect("scan should succeed");
+        assert_eq!(next, PathBuf::from("/output/replaceable.~6~"));
+    }
+
+    #[test]
+    fn backup_target_numbered_mode_scans_for_the_next_free_number() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new()
+            .with_dir("/output")
+            .with_file("/output/replaceable")
+            .with_file("/output/replaceable.~1~");
+
+        let target = backup_target(&dest, BackupMode::Numbered, "~", &fs).expect("backup_target should succeed");
+        assert_eq!(target, PathBuf::from("/output/replaceable.~2~"));
+    }
+
+    #[test]
+    fn backup_target_existing_mode_falls_back_to_simple_with_no_numbered_backup() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new().with_file("/output/replaceable");
+
+        let target = backup_target(&dest, BackupMode::Existing, "~", &fs).expect("backup_target should succeed");
+        assert_eq!(
+            target,
+            PathBuf::from("/output/replaceable~"),
+            "existing mode without a `.~1~` backup should fall back to simple"
+        );
+    }
+
+    #[test]
+    fn backup_target_existing_mode_switches_to_numbered_once_one_exists() {
+        let dest: PathBuf = "/output/replaceable".into();
+        let fs = FakeFs::new()
+            .with_dir("/output")
+            .with_file("/output/replaceable")
+            .with_file("/output/replaceable.~1~");
+
+        let target = backup_target(&dest, BackupMode::Existing, "~", &fs).expect("backup_target should succeed");
+        assert_eq!(
+            target,
+            PathBuf::from("/output/replaceable.~2~"),
+            "existing mode should switch to numbered once a `.~1~` backup is present"
+        );
+    }
 }
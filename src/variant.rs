@@ -0,0 +1,158 @@
+//! Host- and OS-conditional variants of a dotfile, following the
+//! `<base>##host.<value>` / `<base>##os.<value>` filename convention. This
+//! lets a single input tree carry machine-specific files (e.g.
+//! `gitconfig##host.laptop`, `gitconfig##os.linux`) and have only the variant
+//! matching the current machine linked to the untagged destination name.
+
+use std::collections::HashMap;
+use std::env;
+
+use crate::fs::DirEntry;
+
+/// A variant tag parsed from a file name's `##` suffix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tag {
+    Host(String),
+    Os(String),
+}
+
+impl Tag {
+    /// Higher specificity wins when several variants match the same machine.
+    fn specificity(tag: &Option<Tag>) -> u8 {
+        match tag {
+            Some(Tag::Host(_)) => 2,
+            Some(Tag::Os(_)) => 1,
+            None => 0,
+        }
+    }
+
+    /// Whether this tag matches the current `host`/`os`.
+    fn matches(tag: &Option<Tag>, host: &str, os: &str) -> bool {
+        match tag {
+            None => true,
+            Some(Tag::Host(value)) => value == host,
+            Some(Tag::Os(value)) => value == os,
+        }
+    }
+}
+
+/// Split a file name into its base name and an optional variant `Tag`.
+/// Names without a recognized `##host.<value>`/`##os.<value>` suffix are
+/// returned unchanged with no tag.
+fn parse(name: &str) -> (&str, Option<Tag>) {
+    let Some((base, tag)) = name.split_once("##") else {
+        return (name, None);
+    };
+
+    match tag.split_once('.') {
+        Some(("host", value)) => (base, Some(Tag::Host(value.to_string()))),
+        Some(("os", value)) => (base, Some(Tag::Os(value.to_string()))),
+        _ => (name, None),
+    }
+}
+
+/// Group `entries` by their tag-stripped base name and, for each group, keep
+/// only the most specific variant matching the current machine. Variants
+/// that don't match are skipped entirely; entries with no tag always match.
+///
+/// Returns the surviving entries paired with the (tag-stripped) file name
+/// they should be linked to, in the order their base name was first seen.
+pub(crate) fn resolve(entries: Vec<DirEntry>) -> Vec<(DirEntry, String)> {
+    let host = current_host();
+    let os = env::consts::OS;
+
+    let mut order = Vec::new();
+    let mut groups: HashMap<String, Vec<(DirEntry, Option<Tag>)>> = HashMap::new();
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let (base, tag) = parse(&name);
+        let base = base.to_string();
+
+        if !groups.contains_key(&base) {
+            order.push(base.clone());
+        }
+        groups.entry(base).or_default().push((entry, tag));
+    }
+
+    order
+        .into_iter()
+        .filter_map(|base| {
+            let variants = groups.remove(&base)?;
+            let (entry, _) = variants
+                .into_iter()
+                .filter(|(_, tag)| Tag::matches(tag, &host, os))
+                .max_by_key(|(_, tag)| Tag::specificity(tag))?;
+
+            Some((entry, base))
+        })
+        .collect()
+}
+
+/// Resolve the current machine's hostname, used to match `##host.<value>` variants.
+fn current_host() -> String {
+    gethostname::gethostname().to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::fs::{FakeFs, Fs};
+
+    /// Build `DirEntry`s for `names` (placed under `/input`) via `FakeFs`,
+    /// since `DirEntry` has no public constructor outside `fs`.
+    fn entries(names: &[&str]) -> Vec<DirEntry> {
+        let mut fs = FakeFs::new().with_dir("/input");
+        for name in names {
+            fs = fs.with_file(format!("/input/{name}"));
+        }
+
+        fs.read_dir(&PathBuf::from("/input")).expect("read_dir to succeed")
+    }
+
+    #[test]
+    fn matching_tag_wins() {
+        let os = env::consts::OS;
+        let resolved = resolve(entries(&[&format!("gitconfig##os.{os}")]));
+
+        assert_eq!(resolved.len(), 1, "the matching os variant should be kept");
+        assert_eq!(resolved[0].1, "gitconfig", "the dest name should have its tag stripped");
+    }
+
+    #[test]
+    fn no_match_skips_the_group() {
+        let resolved = resolve(entries(&["gitconfig##os.plan9"]));
+
+        assert!(resolved.is_empty(), "a group with no matching variant should be skipped entirely");
+    }
+
+    #[test]
+    fn host_beats_os() {
+        let host = current_host();
+        let os = env::consts::OS;
+        let resolved = resolve(entries(&[
+            &format!("gitconfig##os.{os}"),
+            &format!("gitconfig##host.{host}"),
+        ]));
+
+        assert_eq!(resolved.len(), 1, "only one variant should survive");
+        assert_eq!(
+            resolved[0].0.file_name().to_string_lossy(),
+            format!("gitconfig##host.{host}"),
+            "the more specific host variant should win over the os variant"
+        );
+    }
+
+    #[test]
+    fn untagged_fallback() {
+        let resolved = resolve(entries(&["gitconfig##os.plan9", "gitconfig"]));
+
+        assert_eq!(resolved.len(), 1, "only one variant should survive");
+        assert_eq!(
+            resolved[0].0.file_name().to_string_lossy(),
+            "gitconfig",
+            "the untagged entry should win since the os variant doesn't match"
+        );
+    }
+}